@@ -0,0 +1,146 @@
+//! Built-in test-signal generator / calibration mode.
+//!
+//! Rather than capturing a real device, this synthesizes its own audio
+//! in-process - sine tones, a logarithmic sweep, and silence gaps - and feeds
+//! it through the same [`SpectralAnalyzer`] that drives devices during normal
+//! playback. It lets a user dial in the per-band mapping without needing real
+//! music playing. The latency it logs runs from the start of each segment to
+//! the first analyzed block with energy above [`RESPONSE_THRESHOLD`]; it does
+//! not measure when the device itself vibrates.
+
+use crate::spectrum::{SpectralAnalyzer, DEFAULT_BANDS, DEFAULT_BLOCK_SIZE};
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+use tokio::time::sleep;
+
+/// Energy above this value (on any band) counts as a "response detected" for latency measurement.
+const RESPONSE_THRESHOLD: f64 = 0.05;
+
+/// A single segment of the built-in calibration sequence.
+enum TestSignal {
+    /// A steady sine tone, useful for measuring one band's response at one frequency.
+    Tone { hz: f32, duration: Duration },
+    /// A logarithmic sweep from `start_hz` to `end_hz`, useful for mapping response across the whole spectrum.
+    Sweep {
+        start_hz: f32,
+        end_hz: f32,
+        duration: Duration,
+    },
+    /// Silence, used both to let devices settle and as a clean latency marker before the next tone.
+    Silence { duration: Duration },
+}
+
+/// One tone per default band, separated by silence, followed by a full sweep.
+fn default_sequence() -> Vec<TestSignal> {
+    let mut sequence = vec![TestSignal::Silence {
+        duration: Duration::from_millis(500),
+    }];
+
+    for band in &DEFAULT_BANDS {
+        let hz = (band.low_hz + band.high_hz) / 2.0;
+        sequence.push(TestSignal::Tone {
+            hz,
+            duration: Duration::from_secs(2),
+        });
+        sequence.push(TestSignal::Silence {
+            duration: Duration::from_millis(500),
+        });
+    }
+
+    sequence.push(TestSignal::Sweep {
+        start_hz: 20.0,
+        end_hz: 20_000.0,
+        duration: Duration::from_secs(5),
+    });
+
+    sequence
+}
+
+/// Runs the built-in calibration sequence, sending analyzed band energies down `tx` exactly
+/// as the live audio path does, so the same receive loop drives connected devices.
+pub async fn run_calibration(tx: Sender<Vec<f64>>, sample_rate: f32) {
+    println!("Starting calibration: synthesizing test signals at {sample_rate} Hz...");
+
+    let mut analyzer = SpectralAnalyzer::new(sample_rate, DEFAULT_BLOCK_SIZE, &DEFAULT_BANDS);
+    let block_duration = Duration::from_secs_f32(DEFAULT_BLOCK_SIZE as f32 / sample_rate);
+
+    for signal in default_sequence() {
+        describe(&signal);
+        let onset = Instant::now();
+        let mut responded = false;
+
+        for block in generate_blocks(&signal, sample_rate) {
+            for energies in analyzer.push_samples(&block) {
+                if !responded && energies.iter().any(|energy| *energy > RESPONSE_THRESHOLD) {
+                    responded = true;
+                    println!("  -> band response detected after {:?}", onset.elapsed());
+                }
+                println!("  bands: {energies:?}");
+
+                if let Err(TrySendError::Closed(_)) = tx.try_send(energies) {
+                    println!("Calibration: output channel closed, stopping early.");
+                    return;
+                }
+            }
+
+            sleep(block_duration).await;
+        }
+    }
+
+    if let Some(energies) = analyzer.flush() {
+        let _ = tx.try_send(energies);
+    }
+
+    println!("Calibration complete. Adjust band thresholds/gain based on the readings above.");
+}
+
+/// Renders a test signal's full waveform, chunked into analyzer-sized blocks.
+fn generate_blocks(signal: &TestSignal, sample_rate: f32) -> Vec<Vec<f32>> {
+    render(signal, sample_rate)
+        .chunks(DEFAULT_BLOCK_SIZE)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+/// Synthesizes the raw samples for a test signal at the given sample rate.
+fn render(signal: &TestSignal, sample_rate: f32) -> Vec<f32> {
+    match signal {
+        TestSignal::Tone { hz, duration } => {
+            let sample_count = (duration.as_secs_f32() * sample_rate) as usize;
+            (0..sample_count)
+                .map(|i| (2.0 * PI * hz * i as f32 / sample_rate).sin())
+                .collect()
+        }
+        TestSignal::Sweep {
+            start_hz,
+            end_hz,
+            duration,
+        } => {
+            let sample_count = (duration.as_secs_f32() * sample_rate) as usize;
+            let rate = (end_hz / start_hz).ln() / duration.as_secs_f32();
+            (0..sample_count)
+                .map(|i| {
+                    let t = i as f32 / sample_rate;
+                    let phase = 2.0 * PI * start_hz / rate * ((rate * t).exp() - 1.0);
+                    phase.sin()
+                })
+                .collect()
+        }
+        TestSignal::Silence { duration } => {
+            vec![0.0; (duration.as_secs_f32() * sample_rate) as usize]
+        }
+    }
+}
+
+/// Logs what's about to play so the user can follow along.
+fn describe(signal: &TestSignal) {
+    match signal {
+        TestSignal::Tone { hz, .. } => println!("Playing {hz:.0} Hz test tone..."),
+        TestSignal::Sweep {
+            start_hz, end_hz, ..
+        } => println!("Playing logarithmic sweep {start_hz:.0}-{end_hz:.0} Hz..."),
+        TestSignal::Silence { .. } => println!("Silence..."),
+    }
+}
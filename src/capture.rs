@@ -0,0 +1,157 @@
+//! Direct `cpal` audio capture, feeding a [`SpectralAnalyzer`] straight from the
+//! input stream callback instead of routing through `audio_visualizer`'s
+//! window/global-channel plumbing.
+
+use crate::spectrum::SpectralAnalyzer;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::Device;
+use std::io::{stdin, BufRead};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+
+/// Helps to select available output devices - useful for identifying what's currently
+/// playing audio, so its matching loopback/monitor input device can be picked below.
+pub fn list_output_devs() -> Vec<(String, Device)> {
+    let host = cpal::default_host();
+    type DeviceName = String;
+    let mut devs: Vec<(DeviceName, Device)> = host
+        .output_devices()
+        .unwrap()
+        .map(|dev| {
+            (
+                dev.name().unwrap_or_else(|_| String::from("<unknown>")),
+                dev,
+            )
+        })
+        .collect();
+    devs.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+    devs
+}
+
+/// Helps to select the output device whose audio you intend to capture.
+pub fn select_output_dev() -> Device {
+    let mut devs = list_output_devs();
+    assert!(!devs.is_empty(), "no output devices found!");
+    if devs.len() == 1 {
+        return devs.remove(0).1;
+    }
+    println!("Type the number of the output device audio is playing to, and press enter.");
+    devs.iter().enumerate().for_each(|(i, (name, dev))| {
+        println!(
+            "  [{}] {} {:?}",
+            i,
+            name,
+            dev.default_output_config().unwrap()
+        );
+    });
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input).unwrap();
+    let index = input[0..1].parse::<usize>().unwrap();
+    devs.remove(index).1
+}
+
+/// Helps to select available input devices - a microphone, or a loopback/monitor source.
+pub fn list_input_devs() -> Vec<(String, Device)> {
+    let host = cpal::default_host();
+    type DeviceName = String;
+    let mut devs: Vec<(DeviceName, Device)> = host
+        .input_devices()
+        .unwrap()
+        .map(|dev| {
+            (
+                dev.name().unwrap_or_else(|_| String::from("<unknown>")),
+                dev,
+            )
+        })
+        .collect();
+    devs.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+    devs
+}
+
+/// Helps to select the input device audio should be captured from.
+pub fn select_input_dev() -> Device {
+    let mut devs = list_input_devs();
+    assert!(!devs.is_empty(), "no input devices found!");
+    if devs.len() == 1 {
+        return devs.remove(0).1;
+    }
+    println!("Type the number of the input device to capture from (a microphone, or a loopback/monitor source), and press enter.");
+    devs.iter().enumerate().for_each(|(i, (name, dev))| {
+        println!(
+            "  [{}] {} {:?}",
+            i,
+            name,
+            dev.default_input_config().unwrap()
+        );
+    });
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input).unwrap();
+    let index = input[0..1].parse::<usize>().unwrap();
+    devs.remove(index).1
+}
+
+/// Opens an input stream on `device`, feeding every captured block into `analyzer` and
+/// sending each completed block's per-band energy vector down `tx`. The returned stream
+/// must be kept alive (held, not dropped) for capture to continue.
+///
+/// Dispatches on the device's actual sample format - commonly `I16` on WASAPI-backed
+/// inputs - rather than assuming `F32`, converting to `f32` before handing samples to
+/// the analyzer.
+pub fn start_capture(
+    device: Device,
+    mut analyzer: SpectralAnalyzer,
+    tx: Sender<Vec<f64>>,
+) -> anyhow::Result<cpal::Stream> {
+    let supported_config = device.default_input_config()?;
+    let sample_format = supported_config.sample_format();
+    let config = supported_config.config();
+    let err_fn = |err| eprintln!("Audio capture stream error: {err}");
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |direct_values: &[f32], _: &cpal::InputCallbackInfo| {
+                feed_analyzer(&mut analyzer, direct_values, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |direct_values: &[i16], _: &cpal::InputCallbackInfo| {
+                let converted: Vec<f32> = direct_values
+                    .iter()
+                    .map(|&sample| sample as f32 / i16::MAX as f32)
+                    .collect();
+                feed_analyzer(&mut analyzer, &converted, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &config,
+            move |direct_values: &[u16], _: &cpal::InputCallbackInfo| {
+                let converted: Vec<f32> = direct_values
+                    .iter()
+                    .map(|&sample| (sample as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0))
+                    .collect();
+                feed_analyzer(&mut analyzer, &converted, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        other => anyhow::bail!("unsupported input sample format: {other:?}"),
+    };
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/// Pushes captured samples into the analyzer and sends every completed block onward.
+fn feed_analyzer(analyzer: &mut SpectralAnalyzer, samples: &[f32], tx: &Sender<Vec<f64>>) {
+    for band_energies in analyzer.push_samples(samples) {
+        if let Err(TrySendError::Closed(_)) = tx.try_send(band_energies) {
+            println!("Error while sending to channel... closed!");
+        }
+    }
+}
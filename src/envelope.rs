@@ -0,0 +1,133 @@
+//! Configurable smoothing: an attack/release envelope follower applied to computed
+//! per-band intensities, plus the knobs (sample limit, output rate, noise gate, gain)
+//! needed to dial in responsiveness per device without recompiling.
+
+use std::time::Duration;
+
+/// Tunable knobs for how raw per-band energies get turned into device intensities.
+pub struct EnvelopeConfig {
+    /// The most completed analysis blocks averaged together per envelope step (fewer when
+    /// fewer are queued).
+    pub sample_limit: usize,
+    /// How often to emit vibration commands.
+    pub tick_rate: Duration,
+    /// One-pole smoothing time constant applied while intensity is rising.
+    pub attack: Duration,
+    /// One-pole smoothing time constant applied while intensity is falling.
+    pub release: Duration,
+    /// Intensities below this value snap to zero, so devices don't buzz on the noise floor.
+    pub noise_gate: f64,
+    /// Master gain multiplier applied after averaging, before the envelope and noise gate.
+    pub gain: f64,
+}
+
+impl Default for EnvelopeConfig {
+    fn default() -> Self {
+        EnvelopeConfig {
+            sample_limit: 16,
+            tick_rate: Duration::from_millis(35),
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(250),
+            noise_gate: 0.02,
+            gain: 1.0,
+        }
+    }
+}
+
+impl EnvelopeConfig {
+    /// Parses `--key=value` style CLI args (e.g. `--attack-ms=10 --gain=2.5`), falling back
+    /// to defaults for anything unspecified.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Self {
+        let mut config = EnvelopeConfig::default();
+
+        for arg in args {
+            let Some((key, value)) = arg.strip_prefix("--").and_then(|rest| rest.split_once('='))
+            else {
+                continue;
+            };
+
+            match key {
+                "sample-limit" => match value.parse::<usize>() {
+                    Ok(0) | Err(_) => {
+                        println!("Ignoring invalid --sample-limit value: {value} (must be >= 1)")
+                    }
+                    Ok(v) => config.sample_limit = v,
+                },
+                "tick-ms" => match value.parse::<u64>() {
+                    Ok(0) | Err(_) => {
+                        println!("Ignoring invalid --tick-ms value: {value} (must be >= 1)")
+                    }
+                    Ok(v) => config.tick_rate = Duration::from_millis(v),
+                },
+                "attack-ms" => match value.parse() {
+                    Ok(v) => config.attack = Duration::from_millis(v),
+                    Err(_) => println!("Ignoring invalid --attack-ms value: {value}"),
+                },
+                "release-ms" => match value.parse() {
+                    Ok(v) => config.release = Duration::from_millis(v),
+                    Err(_) => println!("Ignoring invalid --release-ms value: {value}"),
+                },
+                "noise-gate" => match value.parse() {
+                    Ok(v) => config.noise_gate = v,
+                    Err(_) => println!("Ignoring invalid --noise-gate value: {value}"),
+                },
+                "gain" => match value.parse() {
+                    Ok(v) => config.gain = v,
+                    Err(_) => println!("Ignoring invalid --gain value: {value}"),
+                },
+                // Read by `midi::mapping_from_args`.
+                "midi-map" => {}
+                _ => println!("Ignoring unknown config flag: --{key}"),
+            }
+        }
+
+        config
+    }
+}
+
+/// A one-pole attack/release envelope follower, applied independently per band.
+pub struct EnvelopeFollower {
+    attack_coeff: f64,
+    release_coeff: f64,
+    noise_gate: f64,
+    current: Vec<f64>,
+}
+
+impl EnvelopeFollower {
+    pub fn new(config: &EnvelopeConfig, band_count: usize) -> Self {
+        EnvelopeFollower {
+            attack_coeff: one_pole_coeff(config.attack, config.tick_rate),
+            release_coeff: one_pole_coeff(config.release, config.tick_rate),
+            noise_gate: config.noise_gate,
+            current: vec![0.0; band_count],
+        }
+    }
+
+    /// Steps the envelope forward given a new target intensity per band - fast rise, slower
+    /// fall, like a typical audio envelope follower - and returns the smoothed, gated result.
+    pub fn step(&mut self, targets: &[f64]) -> &[f64] {
+        for (current, target) in self.current.iter_mut().zip(targets) {
+            let coeff = if *target > *current {
+                self.attack_coeff
+            } else {
+                self.release_coeff
+            };
+            *current += (*target - *current) * coeff;
+
+            if *current < self.noise_gate {
+                *current = 0.0;
+            }
+        }
+        &self.current
+    }
+}
+
+/// Converts a time constant into a one-pole smoothing coefficient for a given tick rate.
+fn one_pole_coeff(time_constant: Duration, tick_rate: Duration) -> f64 {
+    if time_constant.is_zero() {
+        return 1.0;
+    }
+    let dt = tick_rate.as_secs_f64();
+    let tau = time_constant.as_secs_f64();
+    1.0 - (-dt / tau).exp()
+}
@@ -0,0 +1,196 @@
+//! Optional MIDI control subsystem: translates note-on velocity and control-change values
+//! into vibration commands sent directly to connected Buttplug devices. It runs alongside
+//! the selected audio-driven mode; its intensities are not mixed with the audio loop's, so
+//! whichever source sent a device its latest command sets that device's intensity.
+
+use buttplug::client::{ButtplugClientDevice, ScalarValueCommand};
+use midir::{MidiInput, MidiInputConnection, MidiInputPort};
+use std::io::{stdin, BufRead};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Maps a MIDI channel (and, optionally, a specific note or CC controller number on that
+/// channel) to the connected Buttplug device that should receive its intensity. A mapping
+/// with `note_or_controller: None` acts as a wildcard, matching anything on `channel` that
+/// isn't claimed by a more specific entry - this is what lets a sequencer driving multiple
+/// devices from distinct notes on one channel route them differently.
+pub struct MidiMapping {
+    pub channel: u8,
+    pub note_or_controller: Option<u8>,
+    pub device_index: usize,
+}
+
+/// Builds the default mapping: MIDI channel `n` drives device `n` (wrapping around if there
+/// are more channels in use than connected devices), with no note-specific routing.
+pub fn default_mapping(device_count: usize) -> Vec<MidiMapping> {
+    (0u8..16)
+        .map(|channel| MidiMapping {
+            channel,
+            note_or_controller: None,
+            device_index: channel as usize % device_count.max(1),
+        })
+        .collect()
+}
+
+/// Builds the MIDI mapping from a `--midi-map=...` CLI flag, falling back to
+/// [`default_mapping`] if it isn't present.
+///
+/// The flag value is a comma-separated list of `channel[:note_or_controller]=device_index`
+/// entries, e.g. `--midi-map=0:60=0,0:21=1,1=2` routes note 60 on channel 0 to device 0,
+/// CC 21 on channel 0 to device 1, and anything else on channel 1 to device 2.
+pub fn mapping_from_args(args: impl Iterator<Item = String>, device_count: usize) -> Vec<MidiMapping> {
+    for arg in args {
+        if let Some(spec) = arg.strip_prefix("--midi-map=") {
+            return parse_mapping_spec(spec);
+        }
+    }
+    default_mapping(device_count)
+}
+
+/// Parses a `--midi-map` flag value into mapping entries, skipping (and logging) any
+/// entry that doesn't parse rather than rejecting the whole flag.
+fn parse_mapping_spec(spec: &str) -> Vec<MidiMapping> {
+    let mut mapping = Vec::new();
+
+    for entry in spec.split(',') {
+        let Some((key, device_str)) = entry.split_once('=') else {
+            println!("Ignoring invalid --midi-map entry (missing '='): {entry}");
+            continue;
+        };
+        let Ok(device_index) = device_str.parse::<usize>() else {
+            println!("Ignoring invalid --midi-map entry (bad device index): {entry}");
+            continue;
+        };
+
+        let (channel_str, note_str) = match key.split_once(':') {
+            Some((channel, note)) => (channel, Some(note)),
+            None => (key, None),
+        };
+        let Ok(channel) = channel_str.parse::<u8>() else {
+            println!("Ignoring invalid --midi-map entry (bad channel): {entry}");
+            continue;
+        };
+        let note_or_controller = match note_str {
+            None => None,
+            Some(note) => match note.parse::<u8>() {
+                Ok(note_or_controller) => Some(note_or_controller),
+                Err(_) => {
+                    println!("Ignoring invalid --midi-map entry (bad note/controller): {entry}");
+                    continue;
+                }
+            },
+        };
+
+        mapping.push(MidiMapping {
+            channel,
+            note_or_controller,
+            device_index,
+        });
+    }
+
+    mapping
+}
+
+/// Helps to select an available MIDI input port.
+pub fn select_midi_port(midi_in: &MidiInput) -> Option<MidiInputPort> {
+    let ports = midi_in.ports();
+    if ports.is_empty() {
+        println!("No MIDI input ports found.");
+        return None;
+    }
+    if ports.len() == 1 {
+        return Some(ports[0].clone());
+    }
+
+    println!("Type the number of the MIDI input port to use, and press enter.");
+    for (i, port) in ports.iter().enumerate() {
+        let name = midi_in
+            .port_name(port)
+            .unwrap_or_else(|_| String::from("<unknown>"));
+        println!("  [{i}] {name}");
+    }
+    let mut input = String::new();
+    stdin().lock().read_line(&mut input).unwrap();
+    let index = input.trim().parse::<usize>().unwrap();
+    Some(ports[index].clone())
+}
+
+/// Opens `port`, translating note-on/note-off and control-change messages into vibration
+/// commands sent directly to the mapped device. The returned connection must be kept alive
+/// (held, not dropped) for MIDI control to continue.
+pub fn start_midi_control(
+    midi_in: MidiInput,
+    port: &MidiInputPort,
+    mapping: Vec<MidiMapping>,
+    devices: Vec<Arc<ButtplugClientDevice>>,
+) -> anyhow::Result<MidiInputConnection<()>> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(u8, u8, f64)>();
+
+    let connection = midi_in
+        .connect(
+            port,
+            "subwoofer-midi-input",
+            move |_stamp, message, _| {
+                if let Some(event) = parse_message(message) {
+                    let _ = tx.send(event);
+                }
+            },
+            (),
+        )
+        .map_err(|err| anyhow::anyhow!("failed to open MIDI port: {err}"))?;
+
+    tokio::spawn(async move {
+        while let Some((channel, note_or_controller, intensity)) = rx.recv().await {
+            let Some(target) = find_target(&mapping, channel, note_or_controller) else {
+                continue;
+            };
+            let Some(device) = devices.get(target.device_index) else {
+                continue;
+            };
+
+            if let Err(err) = device
+                .vibrate(&ScalarValueCommand::ScalarValue(intensity))
+                .await
+            {
+                eprintln!(
+                    "Failed to send MIDI-driven vibration to device {}: {:?}",
+                    device.name(),
+                    err
+                );
+            }
+        }
+    });
+
+    Ok(connection)
+}
+
+/// Finds the mapping that should handle an incoming event, preferring an entry that names
+/// this exact note/controller over a channel-wide wildcard (`note_or_controller: None`).
+fn find_target(mapping: &[MidiMapping], channel: u8, note_or_controller: u8) -> Option<&MidiMapping> {
+    mapping
+        .iter()
+        .find(|m| m.channel == channel && m.note_or_controller == Some(note_or_controller))
+        .or_else(|| {
+            mapping
+                .iter()
+                .find(|m| m.channel == channel && m.note_or_controller.is_none())
+        })
+}
+
+/// Parses a raw MIDI message into a `(channel, note_or_controller, intensity)` triple, if
+/// it's one we react to. Note-on maps velocity to intensity, note-off (including
+/// velocity-0 note-on) snaps that note to zero, and control-change maps its value to
+/// intensity directly.
+fn parse_message(message: &[u8]) -> Option<(u8, u8, f64)> {
+    let &[status, data1, data2] = message else {
+        return None;
+    };
+
+    let channel = status & 0x0F;
+    match status & 0xF0 {
+        0x90 if data2 > 0 => Some((channel, data1, (data2 as f64 / 127.0).clamp(0.0, 1.0))),
+        0x90 | 0x80 => Some((channel, data1, 0.0)),
+        0xB0 => Some((channel, data1, (data2 as f64 / 127.0).clamp(0.0, 1.0))),
+        _ => None,
+    }
+}
@@ -0,0 +1,186 @@
+//! Frequency-domain analysis used to drive per-actuator vibration intensity.
+//!
+//! Incoming audio samples are accumulated into fixed-size blocks, windowed,
+//! and run through a forward real FFT. The resulting bins are grouped into a
+//! small number of named frequency bands (sub-bass, bass, mids, highs, ...),
+//! giving a per-band energy vector that can be mapped directly onto a
+//! device's individual actuators instead of collapsing everything into a
+//! single intensity value.
+
+use num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// A named frequency range, in Hz, that incoming FFT bins are grouped into.
+#[derive(Debug, Clone, Copy)]
+pub struct FrequencyBand {
+    pub name: &'static str,
+    pub low_hz: f32,
+    pub high_hz: f32,
+}
+
+/// Default band layout roughly matching what's felt/heard as "bass" vs. "highs".
+pub const DEFAULT_BANDS: [FrequencyBand; 4] = [
+    FrequencyBand {
+        name: "sub-bass",
+        low_hz: 20.0,
+        high_hz: 60.0,
+    },
+    FrequencyBand {
+        name: "bass",
+        low_hz: 60.0,
+        high_hz: 250.0,
+    },
+    FrequencyBand {
+        name: "mids",
+        low_hz: 250.0,
+        high_hz: 2000.0,
+    },
+    FrequencyBand {
+        name: "highs",
+        low_hz: 2000.0,
+        high_hz: 20_000.0,
+    },
+];
+
+/// Block size used for each FFT analysis window. Must be a power of two.
+pub const DEFAULT_BLOCK_SIZE: usize = 1024;
+
+/// Accumulates samples into fixed-size blocks and turns each completed block
+/// into a per-band energy vector via a windowed real FFT.
+pub struct SpectralAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+    accumulator: Vec<f32>,
+    window: Vec<f32>,
+    window_gain: f32,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex32>,
+    scratch: Vec<Complex32>,
+    block_size: usize,
+    sample_rate: f32,
+    bands: Vec<FrequencyBand>,
+}
+
+impl SpectralAnalyzer {
+    /// Builds an analyzer for the given sample rate, block size, and bands.
+    pub fn new(sample_rate: f32, block_size: usize, bands: &[FrequencyBand]) -> Self {
+        assert!(
+            block_size.is_power_of_two(),
+            "block_size must be a power of two"
+        );
+
+        let mut planner = RealFftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(block_size);
+
+        let window = hann_window(block_size);
+        let window_gain = window.iter().sum::<f32>() / block_size as f32;
+
+        let fft_input = fft.make_input_vec();
+        let fft_output = fft.make_output_vec();
+        let scratch = fft.make_scratch_vec();
+
+        SpectralAnalyzer {
+            fft,
+            accumulator: Vec::with_capacity(block_size * 2),
+            window,
+            window_gain,
+            fft_input,
+            fft_output,
+            scratch,
+            block_size,
+            sample_rate,
+            bands: bands.to_vec(),
+        }
+    }
+
+    /// Feeds newly-captured samples in. Returns one per-band energy vector
+    /// for every block completed as a result (zero, one, or more).
+    pub fn push_samples(&mut self, samples: &[f32]) -> Vec<Vec<f64>> {
+        self.accumulator.extend_from_slice(samples);
+
+        let mut completed = Vec::new();
+        while self.accumulator.len() >= self.block_size {
+            let block: Vec<f32> = self.accumulator.drain(..self.block_size).collect();
+            completed.push(self.analyze_block(&block));
+        }
+        completed
+    }
+
+    /// Zero-pads and analyzes whatever trailing samples remain, for use when
+    /// the input stream ends without filling a final full-size block.
+    pub fn flush(&mut self) -> Option<Vec<f64>> {
+        if self.accumulator.is_empty() {
+            return None;
+        }
+
+        let mut block = std::mem::take(&mut self.accumulator);
+        block.resize(self.block_size, 0.0);
+        Some(self.analyze_block(&block))
+    }
+
+    /// Applies the window, runs the forward FFT, and reduces the resulting
+    /// bins into a clamped `[0,1]` energy value per configured band.
+    fn analyze_block(&mut self, block: &[f32]) -> Vec<f64> {
+        for (i, sample) in block.iter().enumerate() {
+            self.fft_input[i] = sample * self.window[i];
+        }
+
+        self.fft
+            .process_with_scratch(&mut self.fft_input, &mut self.fft_output, &mut self.scratch)
+            .expect("FFT of a fixed-size block should never fail");
+
+        let bin_hz = self.sample_rate / self.block_size as f32;
+        // Base per-bin normalization (window gain and block size) so intensity doesn't scale with N.
+        let base_normalization = self.window_gain * self.block_size as f32;
+
+        self.bands
+            .iter()
+            .map(|band| {
+                // Use ceil on both edges (with the upper edge exclusive) so adjacent bands
+                // partition the bin range instead of double-counting the bin straddling
+                // their shared boundary.
+                let low_bin = (band.low_hz / bin_hz).ceil() as usize;
+                let high_bin = ((band.high_hz / bin_hz).ceil() as usize)
+                    .saturating_sub(1)
+                    .min(self.fft_output.len() - 1);
+                if low_bin > high_bin {
+                    return 0.0;
+                }
+
+                // Sum (not average) bin magnitudes: a real tone concentrates its energy into
+                // a handful of bins near its frequency, so averaging across a wide band (e.g.
+                // "highs" spans hundreds of bins at typical sample rates) would dilute a
+                // full-scale signal down near the noise floor.
+                let bin_count = (high_bin - low_bin + 1) as f32;
+                let summed_magnitude = self.fft_output[low_bin..=high_bin]
+                    .iter()
+                    .map(|bin| bin.norm())
+                    .sum::<f32>();
+
+                // Scale the per-bin normalization by sqrt(bin_count), not bin_count: a
+                // narrowband tone's energy stays concentrated in the same handful of bins
+                // regardless of how wide its band is, so dividing by the full bin count
+                // would dilute it right back to near-nothing (the bug this replaces).
+                // Broadband content (noise, transients, cymbals) does grow with bin count
+                // though, so dividing only by its square root keeps wide bands like "highs"
+                // from pegging to the clamp on ordinary broadband material relative to a
+                // narrow band like "sub-bass" carrying an equally loud tone.
+                let normalization = base_normalization * bin_count.sqrt();
+
+                let energy = (summed_magnitude / normalization) as f64;
+                f64::min(energy, 1.0)
+            })
+            .collect()
+    }
+}
+
+/// A Hann window of length `n`, used to reduce spectral leakage before the FFT.
+fn hann_window(n: usize) -> Vec<f32> {
+    if n == 1 {
+        return vec![1.0];
+    }
+    (0..n)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (n - 1) as f32).cos())
+        .collect()
+}